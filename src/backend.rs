@@ -0,0 +1,262 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::models::{ProcessOptions, ProcessResult, TunnelRedirectResult};
+use crate::{Client, CodlError};
+
+/// A source that can resolve a media URL down to a direct, downloadable URL.
+///
+/// [`CobaltBackend`] is the usual path, talking to a cobalt instance. [`YtDlpBackend`]
+/// shells out to the `yt-dlp` binary instead, which is useful as a fallback for
+/// services a cobalt instance can't (or won't) handle.
+///
+/// `resolve` returns a boxed future rather than being an `async fn` so that backends
+/// can be stored as `Box<dyn DownloadBackend>`, letting callers like
+/// [`Client::download_with_backends`] try a caller-supplied list of backends in order.
+pub trait DownloadBackend {
+    fn resolve<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a ProcessOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<ProcessResult, CodlError>> + Send + 'a>>;
+}
+
+/// Resolves media through a cobalt instance, via an existing [`Client`].
+pub struct CobaltBackend {
+    client: Client,
+}
+
+impl CobaltBackend {
+    pub fn new(client: Client) -> Self {
+        CobaltBackend { client }
+    }
+}
+
+impl DownloadBackend for CobaltBackend {
+    fn resolve<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a ProcessOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<ProcessResult, CodlError>> + Send + 'a>> {
+        Box::pin(async move { self.client.process_with_options(url, options.clone()).await })
+    }
+}
+
+#[derive(Deserialize)]
+struct YtDlpFormat {
+    format_id: String,
+    url: String,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    height: Option<u64>,
+    filesize: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct YtDlpInfo {
+    title: String,
+    ext: String,
+    formats: Vec<YtDlpFormat>,
+}
+
+/// Resolves media by shelling out to the `yt-dlp` binary and parsing its JSON info
+/// dump, for services a cobalt instance can't handle.
+pub struct YtDlpBackend {
+    binary: String,
+}
+
+impl Default for YtDlpBackend {
+    fn default() -> Self {
+        YtDlpBackend {
+            binary: "yt-dlp".to_string(),
+        }
+    }
+}
+
+impl YtDlpBackend {
+    /// Use the `yt-dlp` binary found on `PATH`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a `yt-dlp` binary at a specific path instead of relying on `PATH`.
+    pub fn with_binary(binary: String) -> Self {
+        YtDlpBackend { binary }
+    }
+
+    fn is_present(codec: &Option<String>) -> bool {
+        codec.as_deref().map(|c| c != "none").unwrap_or(false)
+    }
+
+    /// Pick the best format for the requested options: the highest-resolution muxed
+    /// (audio+video) format no taller than `video_quality` when downloading video, or
+    /// the largest audio-only format when `download_mode` is `"audio"`. If no muxed
+    /// format fits under `video_quality`, falls back to the tallest video-only format
+    /// that still respects the cap, and only drops the cap entirely if even that is
+    /// empty.
+    fn choose_format<'a>(
+        formats: &'a [YtDlpFormat],
+        options: &ProcessOptions,
+    ) -> Option<&'a YtDlpFormat> {
+        if options.download_mode.as_deref() == Some("audio") {
+            return formats
+                .iter()
+                .filter(|f| Self::is_present(&f.acodec) && !Self::is_present(&f.vcodec))
+                .max_by_key(|f| f.filesize.unwrap_or(0));
+        }
+
+        let target_height = options
+            .video_quality
+            .as_deref()
+            .and_then(|q| q.parse::<u64>().ok());
+
+        let within_quality =
+            |f: &&YtDlpFormat| target_height.map_or(true, |h| f.height.unwrap_or(0) <= h);
+
+        let muxed = formats
+            .iter()
+            .filter(|f| Self::is_present(&f.vcodec) && Self::is_present(&f.acodec))
+            .filter(within_quality);
+
+        muxed
+            .max_by_key(|f| f.height.unwrap_or(0))
+            .or_else(|| {
+                formats
+                    .iter()
+                    .filter(|f| Self::is_present(&f.vcodec))
+                    .filter(within_quality)
+                    .max_by_key(|f| f.height.unwrap_or(0))
+            })
+            .or_else(|| {
+                formats
+                    .iter()
+                    .filter(|f| Self::is_present(&f.vcodec))
+                    .max_by_key(|f| f.height.unwrap_or(0))
+            })
+    }
+}
+
+impl DownloadBackend for YtDlpBackend {
+    fn resolve<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a ProcessOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<ProcessResult, CodlError>> + Send + 'a>> {
+        Box::pin(async move {
+            let output = Command::new(&self.binary)
+                .arg("--dump-single-json")
+                .arg(url)
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                return Err(CodlError::CobaltError(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+
+            let info: YtDlpInfo = serde_json::from_slice(&output.stdout)?;
+            let format =
+                Self::choose_format(&info.formats, options).ok_or(CodlError::BadResponseError)?;
+
+            Ok(ProcessResult::TunnelRedirect(TunnelRedirectResult {
+                status: "tunnel".to_string(),
+                url: format.url.clone(),
+                filename: format!("{}.{}", info.title, info.ext),
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_format(
+        format_id: &str,
+        vcodec: Option<&str>,
+        acodec: Option<&str>,
+        height: Option<u64>,
+        filesize: Option<u64>,
+    ) -> YtDlpFormat {
+        YtDlpFormat {
+            format_id: format_id.to_string(),
+            url: format!("https://example.com/{format_id}"),
+            vcodec: vcodec.map(str::to_string),
+            acodec: acodec.map(str::to_string),
+            height,
+            filesize,
+        }
+    }
+
+    #[test]
+    fn test_choose_format_audio_mode_excludes_muxed_formats() {
+        let formats = vec![
+            make_format("137", Some("avc1"), Some("none"), Some(1080), Some(5_000_000)),
+            make_format("18", Some("avc1"), Some("mp4a"), Some(360), Some(1_000_000)),
+            make_format("140", Some("none"), Some("mp4a"), None, Some(500_000)),
+            make_format("251", Some("none"), Some("opus"), None, Some(800_000)),
+        ];
+        let options = ProcessOptions {
+            download_mode: Some("audio".to_string()),
+            ..ProcessOptions::default()
+        };
+
+        let chosen = YtDlpBackend::choose_format(&formats, &options).unwrap();
+
+        assert_eq!(chosen.format_id, "251");
+    }
+
+    #[test]
+    fn test_choose_format_video_mode_prefers_muxed_within_quality() {
+        let formats = vec![
+            make_format("137", Some("avc1"), Some("none"), Some(1080), Some(5_000_000)),
+            make_format("18", Some("avc1"), Some("mp4a"), Some(360), Some(1_000_000)),
+            make_format("22", Some("avc1"), Some("mp4a"), Some(720), Some(3_000_000)),
+        ];
+        let options = ProcessOptions {
+            video_quality: Some("720".to_string()),
+            ..ProcessOptions::default()
+        };
+
+        let chosen = YtDlpBackend::choose_format(&formats, &options).unwrap();
+
+        assert_eq!(chosen.format_id, "22");
+    }
+
+    #[test]
+    fn test_choose_format_video_mode_falls_back_to_video_only() {
+        let formats = vec![make_format(
+            "137",
+            Some("avc1"),
+            Some("none"),
+            Some(1080),
+            Some(5_000_000),
+        )];
+        let options = ProcessOptions::default();
+
+        let chosen = YtDlpBackend::choose_format(&formats, &options).unwrap();
+
+        assert_eq!(chosen.format_id, "137");
+    }
+
+    #[test]
+    fn test_choose_format_video_only_fallback_still_respects_quality_cap() {
+        let formats = vec![
+            make_format("137", Some("avc1"), Some("mp4a"), Some(1080), Some(5_000_000)),
+            make_format("401", Some("av01"), Some("none"), Some(4320), Some(9_000_000)),
+            make_format("134", Some("avc1"), Some("none"), Some(360), Some(1_000_000)),
+        ];
+        let options = ProcessOptions {
+            video_quality: Some("480".to_string()),
+            ..ProcessOptions::default()
+        };
+
+        let chosen = YtDlpBackend::choose_format(&formats, &options).unwrap();
+
+        assert_eq!(chosen.format_id, "134");
+    }
+}
@@ -3,8 +3,11 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+use backend::DownloadBackend;
+use bytes::Bytes;
 use crate::models::DownloadResult;
-use models::{ProcessOptions, ProcessResult, ServerInfo};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use models::{DownloadStream, ProcessOptions, ProcessResult, ServerInfo};
 use reqwest::header::HeaderValue;
 use reqwest::Response;
 use reqwest::{
@@ -12,9 +15,19 @@ use reqwest::{
     Client as HttpClient,
 };
 use serde_json::{json, Value};
+use std::path::Path;
+use std::pin::Pin;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use url::Url;
 
+/// How many picker items to download at once in [`Client::download_all_with_options`].
+const DOWNLOAD_ALL_CONCURRENCY: usize = 4;
+
+pub mod backend;
 pub mod models;
+pub mod pool;
 
 #[derive(Error, Debug)]
 pub enum CodlError {
@@ -28,14 +41,126 @@ pub enum CodlError {
     BadResponseError,
     #[error("cobalt error {0}")]
     CobaltError(String),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("cobalt api request timed out")]
+    ApiTimedOut,
+    #[error("media download stalled and timed out")]
+    DownloadTimedOut,
+    #[error("no pooled instance supports this service")]
+    NoSupportedInstance,
 }
 
 /// An instance of a client for downloading things from cobalt
+#[derive(Clone)]
 pub struct Client {
     /// HTTP client which requests to the cobalt server are made with
     client: HttpClient,
     /// The cobalt instance URL
     instance_url: String,
+    /// Deadline for a whole cobalt API request (`info`/`process`). See
+    /// [`ClientBuilder::timeout`].
+    api_timeout: Option<Duration>,
+    /// Idle timeout between chunks of a media download. See
+    /// [`ClientBuilder::download_timeout`].
+    download_timeout: Option<Duration>,
+}
+
+/// A builder for a [`Client`], allowing things like request timeouts to be configured
+/// before the underlying HTTP client is built.
+///
+/// Note: gating TLS backend selection behind `default-tls`/`rustls-tls-*` Cargo
+/// features (forwarded to `reqwest`) is explicitly **out of scope** for this crate
+/// right now — there's no `Cargo.toml` in this tree to declare such features on, so
+/// `ClientBuilder` has no way to expose one. TLS backend selection is whatever
+/// `reqwest`'s own default is for the version this crate is built against. Revisit
+/// once this crate has a manifest of its own.
+pub struct ClientBuilder {
+    instance_url: String,
+    auth_token: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    download_timeout: Option<Duration>,
+}
+
+impl ClientBuilder {
+    /// Start building a client for the given cobalt instance.
+    pub fn new(instance_url: String) -> Self {
+        ClientBuilder {
+            instance_url,
+            auth_token: None,
+            timeout: None,
+            connect_timeout: None,
+            download_timeout: None,
+        }
+    }
+
+    /// Set the API token to authenticate with the instance.
+    pub fn auth_token(mut self, auth_token: String) -> Self {
+        self.auth_token = Some(auth_token);
+        self
+    }
+
+    /// Set a deadline for each cobalt API request (`info`/`process`), so a hung
+    /// cobalt instance returns [`CodlError::ApiTimedOut`] instead of hanging
+    /// forever.
+    ///
+    /// The deadline covers sending the request and receiving the response headers;
+    /// reading the (small, JSON) body afterwards is not timed. It is deliberately
+    /// **not** applied to media downloads, since a
+    /// multi-hundred-MB download streamed over several minutes would otherwise be
+    /// aborted mid-transfer by a deadline sized for a JSON response, even while data
+    /// is actively flowing. See [`ClientBuilder::download_timeout`] for a
+    /// download-appropriate idle timeout instead.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for just the connection phase of every request made by the
+    /// client.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Set an idle timeout for media downloads: if the response headers, or a new
+    /// chunk of the response body, don't arrive within this long, the download fails with
+    /// [`CodlError::DownloadTimedOut`] rather than hanging on a stalled CDN. Unlike
+    /// [`ClientBuilder::timeout`], this resets on every chunk, so it doesn't cap how
+    /// long a (slow but steady) download can take overall.
+    pub fn download_timeout(mut self, download_timeout: Duration) -> Self {
+        self.download_timeout = Some(download_timeout);
+        self
+    }
+
+    /// Build the [`Client`].
+    pub fn build(self) -> Result<Client, CodlError> {
+        let mut default_headers = HeaderMap::new();
+
+        if let Some(token) = self.auth_token {
+            default_headers.insert(
+                header::AUTHORIZATION,
+                format!("Api-Key {}", token)
+                    .parse()
+                    .map_err(|_| CodlError::BadApiToken)?,
+            );
+        }
+        default_headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+        let mut http_builder = HttpClient::builder().default_headers(default_headers);
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            http_builder = http_builder.connect_timeout(connect_timeout);
+        }
+
+        Ok(Client {
+            client: http_builder.build()?,
+            instance_url: self.instance_url,
+            api_timeout: self.timeout,
+            download_timeout: self.download_timeout,
+        })
+    }
 }
 
 impl Client {
@@ -51,24 +176,45 @@ impl Client {
     ///     Some("00000000-0000-0000-0000-000000000000".to_string())).unwrap();
     /// ```
     pub fn new(instance_url: String, auth_token: Option<String>) -> Result<Self, CodlError> {
-        let mut default_headers = HeaderMap::new();
+        let mut builder = ClientBuilder::new(instance_url);
 
         if let Some(token) = auth_token {
-            default_headers.insert(
-                header::AUTHORIZATION,
-                format!("Api-Key {}", token)
-                    .parse()
-                    .map_err(|_| CodlError::BadApiToken)?,
-            );
+            builder = builder.auth_token(token);
         }
-        default_headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
 
-        Ok(Client {
-            client: HttpClient::builder()
-                .default_headers(default_headers)
-                .build()?,
-            instance_url,
-        })
+        builder.build()
+    }
+
+    /// Create a [`ClientBuilder`] to configure a client with things like request
+    /// timeouts before building it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use codl::Client;
+    /// use std::time::Duration;
+    ///
+    /// let my_client = Client::builder("http://127.0.0.1:9000".to_string())
+    ///     .timeout(Duration::from_secs(30))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder(instance_url: String) -> ClientBuilder {
+        ClientBuilder::new(instance_url)
+    }
+
+    /// Send a cobalt API request, applying [`ClientBuilder::timeout`] as a deadline
+    /// for receiving the response headers if one was configured; reading the body
+    /// afterwards is not covered. Not used for media downloads,
+    /// which have their own idle timeout (see [`Client::stream_url`]).
+    async fn send_timed(&self, req: reqwest::RequestBuilder) -> Result<Response, CodlError> {
+        match self.api_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, req.send())
+                .await
+                .map_err(|_| CodlError::ApiTimedOut)?
+                .map_err(CodlError::from),
+            None => req.send().await.map_err(CodlError::from),
+        }
     }
 
     async fn check_for_error(&self, res: Response) -> Result<Response, CodlError> {
@@ -112,9 +258,7 @@ impl Client {
     /// ```
     pub async fn info(&self) -> Result<ServerInfo, CodlError> {
         let res = self
-            .client
-            .get(&self.instance_url)
-            .send()
+            .send_timed(self.client.get(&self.instance_url))
             .await?
             .error_for_status()?;
 
@@ -152,10 +296,7 @@ impl Client {
 
         let res = self
             .check_for_error(
-                self.client
-                    .post(&self.instance_url)
-                    .json(&body)
-                    .send()
+                self.send_timed(self.client.post(&self.instance_url).json(&body))
                     .await?,
             )
             .await?;
@@ -196,37 +337,215 @@ impl Client {
             .await
     }
 
-    /// Download media using the cobalt instance with manual options.
+    /// Resolve a processed result down to a single direct media URL and filename.
     ///
     /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
-    pub async fn download_with_options(
+    fn resolve_download_target_from_result(
+        res: ProcessResult,
+    ) -> Result<(String, String), CodlError> {
+        match res {
+            ProcessResult::TunnelRedirect(t) => Ok((t.url, t.filename)),
+            ProcessResult::Picker(p) => {
+                if let Some(picker_item) = p.picker.first() {
+                    Ok((picker_item.url.clone(), p.audio_filename))
+                } else {
+                    Err(CodlError::BadResponseError)
+                }
+            }
+        }
+    }
+
+    /// Process media then resolve it down to a single direct media URL and filename.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    async fn resolve_download_target(
         &self,
         url: &str,
         options: ProcessOptions,
-    ) -> Result<DownloadResult, CodlError> {
+    ) -> Result<(String, String), CodlError> {
         let res = self.process_with_options(url, options).await?;
+        Self::resolve_download_target_from_result(res)
+    }
 
-        let (url, filename) = {
-            match res {
-                ProcessResult::TunnelRedirect(t) => (t.url, t.filename),
-                ProcessResult::Picker(p) => {
-                    if let Some(picker_item) = p.picker.first() {
-                        (picker_item.url.clone(), p.audio_filename)
-                    } else {
-                        return Err(CodlError::BadResponseError);
-                    }
-                }
-            }
-        };
+    /// Download a direct media URL, already resolved, buffering it into a
+    /// [`DownloadResult`].
+    async fn download_url(
+        &self,
+        download_url: String,
+        filename: String,
+    ) -> Result<DownloadResult, CodlError> {
+        let mut download = self.stream_url(download_url, filename).await?;
+        let mut data = Vec::with_capacity(download.total_size.unwrap_or(0) as usize);
 
-        let download_res = reqwest::get(url).await?.error_for_status()?;
+        while let Some(chunk) = download.stream.next().await {
+            data.extend_from_slice(&chunk?);
+        }
 
         Ok(DownloadResult {
-            data: download_res.bytes().await?,
+            data: Bytes::from(data),
+            filename: download.filename,
+        })
+    }
+
+    /// Fallback extension for a picker item when its own URL doesn't have one, keyed
+    /// on cobalt's `type` field.
+    fn extension_for_media_type(media_type: &str) -> &'static str {
+        match media_type {
+            "photo" => "jpg",
+            "gif" => "gif",
+            "video" => "mp4",
+            _ => "bin",
+        }
+    }
+
+    /// Derive a filename for one item of a picker result from the item's own URL
+    /// where possible, falling back to a guess based on its `media_type`.
+    fn picker_item_filename(index: usize, media_type: &str, item_url: &str) -> String {
+        let ext = Url::parse(item_url)
+            .ok()
+            .and_then(|url| {
+                Path::new(url.path())
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| ext.to_string())
+            })
+            .unwrap_or_else(|| Self::extension_for_media_type(media_type).to_string());
+
+        format!("{media_type}_{index}.{ext}")
+    }
+
+    /// Stream a direct media URL, already resolved, down to a [`DownloadStream`].
+    ///
+    /// If [`ClientBuilder::download_timeout`] was configured, the response headers must
+    /// arrive within that long or this returns [`CodlError::DownloadTimedOut`], and
+    /// each chunk must arrive within that long of the previous one or the stream yields
+    /// [`CodlError::DownloadTimedOut`] — an idle timeout, not a deadline on the
+    /// request as a whole, so it doesn't cut off a download that's still making
+    /// steady progress.
+    async fn stream_url(
+        &self,
+        download_url: String,
+        filename: String,
+    ) -> Result<DownloadStream, CodlError> {
+        let request = self.client.get(download_url).send();
+        let download_res = match self.download_timeout {
+            Some(download_timeout) => tokio::time::timeout(download_timeout, request)
+                .await
+                .map_err(|_| CodlError::DownloadTimedOut)??,
+            None => request.await?,
+        }
+        .error_for_status()?;
+        let total_size = download_res.content_length();
+
+        let chunks = download_res
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(CodlError::from));
+
+        let stream: Pin<Box<dyn Stream<Item = Result<Bytes, CodlError>> + Send>> =
+            match self.download_timeout {
+                Some(download_timeout) => Box::pin(
+                    tokio_stream::StreamExt::timeout(chunks, download_timeout).map(|chunk| {
+                        match chunk {
+                            Ok(chunk) => chunk,
+                            Err(_) => Err(CodlError::DownloadTimedOut),
+                        }
+                    }),
+                ),
+                None => Box::pin(chunks),
+            };
+
+        Ok(DownloadStream {
             filename,
+            total_size,
+            stream,
         })
     }
 
+    /// Start streaming a download with manual options, without buffering it into memory.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_stream_with_options(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<DownloadStream, CodlError> {
+        let (download_url, filename) = self.resolve_download_target(url, options).await?;
+        self.stream_url(download_url, filename).await
+    }
+
+    /// Start streaming a download with default options, without buffering it into memory.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_stream(&self, url: &str) -> Result<DownloadStream, CodlError> {
+        self.download_stream_with_options(url, ProcessOptions::default())
+            .await
+    }
+
+    /// Download media with manual options, writing chunks straight to `writer` as they
+    /// arrive rather than buffering the whole file in memory. `progress`, if given, is
+    /// called after every chunk with the number of bytes written so far and the total
+    /// size (from `Content-Length`), which may be unknown for chunked/HLS tunnels.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_to_writer_with_options<W, F>(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+        writer: &mut W,
+        mut progress: Option<F>,
+    ) -> Result<String, CodlError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(u64, Option<u64>),
+    {
+        let mut download = self.download_stream_with_options(url, options).await?;
+        let mut downloaded: u64 = 0;
+
+        while let Some(chunk) = download.stream.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).await?;
+
+            downloaded += chunk.len() as u64;
+            if let Some(progress) = progress.as_mut() {
+                progress(downloaded, download.total_size);
+            }
+        }
+
+        writer.flush().await?;
+
+        Ok(download.filename)
+    }
+
+    /// Download media with default options, writing chunks straight to `writer`. See
+    /// [`Client::download_to_writer_with_options`] for details.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_to_writer<W, F>(
+        &self,
+        url: &str,
+        writer: &mut W,
+        progress: Option<F>,
+    ) -> Result<String, CodlError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(u64, Option<u64>),
+    {
+        self.download_to_writer_with_options(url, ProcessOptions::default(), writer, progress)
+            .await
+    }
+
+    /// Download media using the cobalt instance with manual options.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_with_options(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<DownloadResult, CodlError> {
+        let (download_url, filename) = self.resolve_download_target(url, options).await?;
+        self.download_url(download_url, filename).await
+    }
+
     /// Download media using the cobalt instance with default options.
     ///
     /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
@@ -234,6 +553,100 @@ impl Client {
         self.download_with_options(url, ProcessOptions::default())
             .await
     }
+
+    /// Download media, trying each of `backends` in order and falling through to the
+    /// next on a [`CodlError::CobaltError`] (unsupported service, rate-limited,
+    /// region-blocked, etc), returning the first success.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_with_backends(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+        backends: &[Box<dyn DownloadBackend>],
+    ) -> Result<DownloadResult, CodlError> {
+        let mut last_err = CodlError::BadResponseError;
+
+        for backend in backends {
+            match backend.resolve(url, &options).await {
+                Ok(res) => {
+                    let (download_url, filename) = Self::resolve_download_target_from_result(res)?;
+                    return self.download_url(download_url, filename).await;
+                }
+                Err(err @ CodlError::CobaltError(_)) => last_err = err,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Download media, falling back to `yt-dlp` (see [`backend::YtDlpBackend`]) if the
+    /// cobalt instance reports a [`CodlError::CobaltError`] (unsupported service,
+    /// rate-limited, region-blocked, etc). A thin wrapper around
+    /// [`Client::download_with_backends`] with the default `[cobalt, yt-dlp]` backend
+    /// order; call that directly to customize the backends or their order.
+    ///
+    /// Please note that for picker items, the first will be chosen. If this isn't what you need, you should `process()` then handle the result accordingly.
+    pub async fn download_with_fallback(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<DownloadResult, CodlError> {
+        let backends: Vec<Box<dyn DownloadBackend>> = vec![
+            Box::new(backend::CobaltBackend::new(self.clone())),
+            Box::new(backend::YtDlpBackend::new()),
+        ];
+
+        self.download_with_backends(url, options, &backends).await
+    }
+
+    /// Download every item of a processed result with manual options, concurrently.
+    ///
+    /// For a [`ProcessResult::TunnelRedirect`] this yields a single result. For a
+    /// [`ProcessResult::Picker`] every [`PickerItem`](models::PickerItem) is
+    /// downloaded (plus the separate audio track, if present), bounded to
+    /// [`DOWNLOAD_ALL_CONCURRENCY`] downloads at a time rather than all at once.
+    pub async fn download_all_with_options(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<Vec<DownloadResult>, CodlError> {
+        let res = self.process_with_options(url, options).await?;
+
+        match res {
+            ProcessResult::TunnelRedirect(t) => {
+                Ok(vec![self.download_url(t.url, t.filename).await?])
+            }
+            ProcessResult::Picker(p) => {
+                let mut downloads: Vec<_> = p
+                    .picker
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let filename = Self::picker_item_filename(i, &item.media_type, &item.url);
+                        self.download_url(item.url.clone(), filename)
+                    })
+                    .collect();
+
+                if !p.audio.is_empty() {
+                    downloads.push(self.download_url(p.audio.clone(), p.audio_filename.clone()));
+                }
+
+                stream::iter(downloads)
+                    .buffer_unordered(DOWNLOAD_ALL_CONCURRENCY)
+                    .try_collect()
+                    .await
+            }
+        }
+    }
+
+    /// Download every item of a processed result with default options. See
+    /// [`Client::download_all_with_options`] for details.
+    pub async fn download_all(&self, url: &str) -> Result<Vec<DownloadResult>, CodlError> {
+        self.download_all_with_options(url, ProcessOptions::default())
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -283,4 +696,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_picker_item_filename_uses_extension_from_url() {
+        assert_eq!(
+            Client::picker_item_filename(0, "video", "https://example.com/media/clip.mp4?x=1"),
+            "video_0.mp4"
+        );
+        assert_eq!(
+            Client::picker_item_filename(2, "photo", "https://example.com/media/img.jpeg"),
+            "photo_2.jpeg"
+        );
+    }
+
+    #[test]
+    fn test_picker_item_filename_falls_back_to_media_type() {
+        assert_eq!(
+            Client::picker_item_filename(1, "photo", "https://example.com/media/noext"),
+            "photo_1.jpg"
+        );
+        assert_eq!(
+            Client::picker_item_filename(0, "gif", "https://example.com/media/noext"),
+            "gif_0.gif"
+        );
+    }
 }
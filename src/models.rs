@@ -1,11 +1,15 @@
 use bytes::Bytes;
+use futures::Stream;
+use std::pin::Pin;
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, TimestampMilliSeconds};
 
+use crate::CodlError;
+
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerInfoCobalt {
     pub version: String,
@@ -16,20 +20,20 @@ pub struct ServerInfoCobalt {
     pub services: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServerInfoGit {
     pub commit: String,
     pub branch: String,
     pub remote: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServerInfo {
     pub cobalt: ServerInfoCobalt,
     pub git: ServerInfoGit,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -115,3 +119,13 @@ pub struct DownloadResult {
     pub data: Bytes,
     pub filename: String,
 }
+
+/// A download in progress, yielded chunk by chunk instead of buffered into memory.
+///
+/// `total_size` comes from the tunnel response's `Content-Length` header and may be
+/// `None` for chunked or HLS tunnels that don't advertise a length up front.
+pub struct DownloadStream {
+    pub filename: String,
+    pub total_size: Option<u64>,
+    pub stream: Pin<Box<dyn Stream<Item = Result<Bytes, CodlError>> + Send>>,
+}
@@ -0,0 +1,284 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::models::{DownloadResult, ProcessOptions, ProcessResult, ServerInfo};
+use crate::{Client, CodlError};
+
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Known hosts for each cobalt service name, used by [`ClientPool::service_for_url`].
+/// A URL matches a service if its host is exactly one of these domains or a subdomain
+/// of one, which is what actually shows up in links people share (`youtu.be`,
+/// `vm.tiktok.com`, `old.reddit.com`, `x.com`, ...).
+const SERVICE_DOMAINS: &[(&str, &[&str])] = &[
+    ("bilibili", &["bilibili.com", "b23.tv"]),
+    ("bluesky", &["bsky.app"]),
+    ("dailymotion", &["dailymotion.com", "dai.ly"]),
+    ("facebook", &["facebook.com", "fb.watch"]),
+    ("instagram", &["instagram.com"]),
+    ("loom", &["loom.com"]),
+    ("ok", &["ok.ru"]),
+    ("pinterest", &["pinterest.com", "pin.it"]),
+    ("reddit", &["reddit.com", "redd.it"]),
+    ("rutube", &["rutube.ru"]),
+    ("snapchat", &["snapchat.com"]),
+    ("soundcloud", &["soundcloud.com"]),
+    ("streamable", &["streamable.com"]),
+    ("tiktok", &["tiktok.com"]),
+    ("tumblr", &["tumblr.com"]),
+    ("twitter", &["twitter.com", "x.com"]),
+    ("vimeo", &["vimeo.com"]),
+    ("vk", &["vk.com"]),
+    ("xiaohongshu", &["xiaohongshu.com", "xhslink.com"]),
+    ("youtube", &["youtube.com", "youtu.be"]),
+];
+
+struct PoolInstance {
+    client: Client,
+    info_cache: Mutex<Option<(ServerInfo, Instant)>>,
+}
+
+/// A pool of cobalt instances that routes each request to whichever instance
+/// advertises support for the target service, transparently retrying the next
+/// healthy instance if the chosen one fails with an HTTP or cobalt error.
+pub struct ClientPool {
+    instances: Vec<PoolInstance>,
+    cache_ttl: Duration,
+}
+
+impl ClientPool {
+    /// Build a pool from `(instance_url, auth_token)` pairs.
+    pub fn new(instances: Vec<(String, Option<String>)>) -> Result<Self, CodlError> {
+        Self::with_cache_ttl(instances, DEFAULT_CACHE_TTL)
+    }
+
+    /// Build a pool, overriding how long each instance's [`ServerInfo`] is cached
+    /// before it's re-probed.
+    pub fn with_cache_ttl(
+        instances: Vec<(String, Option<String>)>,
+        cache_ttl: Duration,
+    ) -> Result<Self, CodlError> {
+        let instances = instances
+            .into_iter()
+            .map(|(instance_url, auth_token)| {
+                Ok(PoolInstance {
+                    client: Client::new(instance_url, auth_token)?,
+                    info_cache: Mutex::new(None),
+                })
+            })
+            .collect::<Result<Vec<_>, CodlError>>()?;
+
+        Ok(ClientPool {
+            instances,
+            cache_ttl,
+        })
+    }
+
+    /// Fetch the [`ServerInfo`] (using the cache where it's still fresh) for every
+    /// instance in the pool, so callers can inspect which services are covered.
+    pub async fn capabilities(&self) -> Vec<Result<ServerInfo, CodlError>> {
+        let mut results = Vec::with_capacity(self.instances.len());
+
+        for instance in &self.instances {
+            results.push(self.info(instance).await);
+        }
+
+        results
+    }
+
+    async fn info(&self, instance: &PoolInstance) -> Result<ServerInfo, CodlError> {
+        let mut cache = instance.info_cache.lock().await;
+
+        if let Some((info, fetched_at)) = cache.as_ref() {
+            if fetched_at.elapsed() < self.cache_ttl {
+                return Ok(info.clone());
+            }
+        }
+
+        let info = instance.client.info().await?;
+        *cache = Some((info.clone(), Instant::now()));
+
+        Ok(info)
+    }
+
+    /// Pull the cobalt service name (e.g. `twitter`, `youtube`, `tiktok`) out of a
+    /// media URL's host, the same way cobalt's own `services` list names them.
+    ///
+    /// Matches against [`SERVICE_DOMAINS`] rather than just taking the host's first
+    /// label, since that would turn `youtu.be` into `"youtu"`, `vm.tiktok.com` into
+    /// `"vm"`, and `x.com` into `"x"`.
+    fn service_for_url(url: &str) -> Option<String> {
+        let parsed = Url::parse(url).ok()?;
+        let host = parsed.host_str()?.to_lowercase();
+
+        SERVICE_DOMAINS
+            .iter()
+            .find(|(_, domains)| {
+                domains
+                    .iter()
+                    .any(|domain| host == *domain || host.ends_with(&format!(".{domain}")))
+            })
+            .map(|(service, _)| service.to_string())
+    }
+
+    /// Instances in probe order that support the service the URL belongs to.
+    async fn candidates(&self, url: &str) -> Vec<&PoolInstance> {
+        let service = Self::service_for_url(url);
+        let mut candidates = Vec::new();
+
+        for instance in &self.instances {
+            let Ok(info) = self.info(instance).await else {
+                continue;
+            };
+
+            let supported = match &service {
+                Some(service) => info
+                    .cobalt
+                    .services
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(service)),
+                None => true,
+            };
+
+            if supported {
+                candidates.push(instance);
+            }
+        }
+
+        candidates
+    }
+
+    /// Process media with manual options, routing to a pooled instance that supports
+    /// the service and retrying the next healthy instance on an HTTP or cobalt error.
+    pub async fn process_with_options(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<ProcessResult, CodlError> {
+        let candidates = self.candidates(url).await;
+
+        if candidates.is_empty() {
+            return Err(CodlError::NoSupportedInstance);
+        }
+
+        let mut last_err = CodlError::BadResponseError;
+
+        for instance in candidates {
+            match instance
+                .client
+                .process_with_options(url, options.clone())
+                .await
+            {
+                Ok(result) => return Ok(result),
+                Err(err @ (CodlError::HttpError(_) | CodlError::CobaltError(_))) => {
+                    last_err = err;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Process media with default options. See
+    /// [`ClientPool::process_with_options`] for details.
+    pub async fn process(&self, url: &str) -> Result<ProcessResult, CodlError> {
+        self.process_with_options(url, ProcessOptions::default())
+            .await
+    }
+
+    /// Download media with manual options, routing to a pooled instance that
+    /// supports the service and retrying the next healthy instance on an HTTP or
+    /// cobalt error.
+    pub async fn download_with_options(
+        &self,
+        url: &str,
+        options: ProcessOptions,
+    ) -> Result<DownloadResult, CodlError> {
+        let candidates = self.candidates(url).await;
+
+        if candidates.is_empty() {
+            return Err(CodlError::NoSupportedInstance);
+        }
+
+        let mut last_err = CodlError::BadResponseError;
+
+        for instance in candidates {
+            match instance
+                .client
+                .download_with_options(url, options.clone())
+                .await
+            {
+                Ok(result) => return Ok(result),
+                Err(err @ (CodlError::HttpError(_) | CodlError::CobaltError(_))) => {
+                    last_err = err;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Download media with default options. See
+    /// [`ClientPool::download_with_options`] for details.
+    pub async fn download(&self, url: &str) -> Result<DownloadResult, CodlError> {
+        self.download_with_options(url, ProcessOptions::default())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_for_url_matches_known_domains() {
+        assert_eq!(
+            ClientPool::service_for_url("https://www.youtube.com/watch?v=abc"),
+            Some("youtube".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://youtu.be/abc"),
+            Some("youtube".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://vm.tiktok.com/abc"),
+            Some("tiktok".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://vt.tiktok.com/abc"),
+            Some("tiktok".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://old.reddit.com/r/rust"),
+            Some("reddit".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://x.com/i/status/123"),
+            Some("twitter".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://twitter.com/i/status/123"),
+            Some("twitter".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://www.facebook.com/watch?v=123"),
+            Some("facebook".to_string())
+        );
+        assert_eq!(
+            ClientPool::service_for_url("https://fb.watch/abc"),
+            Some("facebook".to_string())
+        );
+    }
+
+    #[test]
+    fn test_service_for_url_unknown_host_is_none() {
+        assert_eq!(
+            ClientPool::service_for_url("https://example.com/video"),
+            None
+        );
+    }
+}